@@ -0,0 +1,176 @@
+/*
+Copyright (C) 2024 Ivin Joel Abraham
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+/// Collect open ports and/or Windows event log entries.
+#[derive(Parser, Debug)]
+#[command(name = "rust-winapi", author, version, about, long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Collect listening ports and the processes that own them
+    Ports(PortsArgs),
+    /// Collect Windows event log entries
+    Events(EventsArgs),
+    /// Run the port collector and the event logger together
+    All(AllArgs),
+    /// Decrypt a file written with --encrypt
+    Decrypt(DecryptArgs),
+    /// Keep collecting and serve events/ports over a local TCP socket
+    Serve(ServeArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PortsArgs {
+    /// Where to write the collected process/port data
+    #[arg(long, default_value = "process_ports.json")]
+    pub output: String,
+
+    /// Output encoding for the collected data
+    #[arg(long, value_enum, default_value_t = FormatArg::Json)]
+    pub format: FormatArg,
+
+    /// Keep running and append port/process deltas instead of a one-shot snapshot
+    #[arg(long)]
+    pub monitor: bool,
+
+    /// Seconds between rescans when --monitor is set
+    #[arg(long, default_value_t = 5)]
+    pub interval: u64,
+
+    /// Include CPU, memory, disk, timing, and identity metrics for each process
+    #[arg(long)]
+    pub with_metrics: bool,
+
+    #[command(flatten)]
+    pub encryption: EncryptionArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct EventsArgs {
+    /// Where to write the collected events
+    #[arg(long, default_value = "events.json")]
+    pub output: String,
+
+    /// Output encoding for the collected events
+    #[arg(long, value_enum, default_value_t = FormatArg::Json)]
+    pub format: FormatArg,
+
+    /// Only keep events at or above this severity (1 = Critical/most severe, 5 = Verbose/least severe)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=5))]
+    pub min_level: u32,
+
+    /// Comma-separated list of event log channels to query
+    #[arg(long, value_delimiter = ',', default_value = "Application,System")]
+    pub channels: Vec<String>,
+
+    #[command(flatten)]
+    pub encryption: EncryptionArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct EncryptionArgs {
+    /// Encrypt the output file at rest with AES-CTR (requires --key)
+    #[arg(long, requires = "key")]
+    pub encrypt: bool,
+
+    /// Hex-encoded 16-byte (AES-128) or 32-byte (AES-256) encryption key
+    #[arg(long)]
+    pub key: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DecryptArgs {
+    /// Path to the encrypted file
+    pub input: String,
+
+    /// Where to write the decrypted contents
+    pub output: String,
+
+    /// Hex-encoded key used to encrypt the file
+    #[arg(long)]
+    pub key: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AllArgs {
+    /// Where to write the collected process/port data
+    #[arg(long, default_value = "process_ports.json")]
+    pub ports_output: String,
+
+    /// Where to write the collected events
+    #[arg(long, default_value = "events.json")]
+    pub events_output: String,
+
+    /// Output encoding applied to both the port and event data
+    #[arg(long, value_enum, default_value_t = FormatArg::Json)]
+    pub format: FormatArg,
+
+    /// Keep running and append port/process deltas instead of a one-shot snapshot
+    #[arg(long)]
+    pub monitor: bool,
+
+    /// Seconds between rescans when --monitor is set
+    #[arg(long, default_value_t = 5)]
+    pub interval: u64,
+
+    /// Include CPU, memory, disk, timing, and identity metrics for each process
+    #[arg(long)]
+    pub with_metrics: bool,
+
+    /// Only keep events at or above this severity (1 = Critical/most severe, 5 = Verbose/least severe)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=5))]
+    pub min_level: u32,
+
+    /// Comma-separated list of event log channels to query
+    #[arg(long, value_delimiter = ',', default_value = "Application,System")]
+    pub channels: Vec<String>,
+
+    #[command(flatten)]
+    pub encryption: EncryptionArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// Address to listen on for client connections
+    #[arg(long, default_value = "127.0.0.1:7879")]
+    pub listen: String,
+
+    /// Comma-separated list of event log channels to poll
+    #[arg(long, value_delimiter = ',', default_value = "Application,System")]
+    pub channels: Vec<String>,
+
+    /// Minimum event severity to query from the event log (1 = Critical/most severe, 5 = Verbose/least severe); a client's SUBSCRIBE level can only narrow this further
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=5))]
+    pub min_level: u32,
+
+    /// Seconds between polling the event log for fresh events
+    #[arg(long, default_value_t = 5)]
+    pub poll_interval: u64,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatArg {
+    Json,
+    JsonCompact,
+    Ndjson,
+    MessagePack,
+}
@@ -0,0 +1,205 @@
+/*
+Copyright (C) 2024 Ivin Joel Abraham
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::{Aes128, Aes256};
+use rand::RngCore;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+const BLOCK_SIZE: usize = 16;
+
+/// An AES-128 or AES-256 key, sized by how many bytes were supplied.
+pub enum AesKey {
+    Aes128([u8; 16]),
+    Aes256([u8; 32]),
+}
+
+impl AesKey {
+    pub fn from_bytes(key: &[u8]) -> io::Result<Self> {
+        match key.len() {
+            16 => Ok(AesKey::Aes128(key.try_into().unwrap())),
+            32 => Ok(AesKey::Aes256(key.try_into().unwrap())),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("encryption key must be 16 or 32 bytes, got {other}"),
+            )),
+        }
+    }
+}
+
+/// Parses a hex-encoded 16-byte (AES-128) or 32-byte (AES-256) key.
+pub fn parse_hex_key(hex: &str) -> io::Result<AesKey> {
+    if hex.len() % 2 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "key must have an even number of hex digits",
+        ));
+    }
+
+    let bytes: Result<Vec<u8>, _> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect();
+
+    let bytes = bytes
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid hex key: {e}")))?;
+
+    AesKey::from_bytes(&bytes)
+}
+
+fn encrypt_block(key: &AesKey, block: &mut [u8; BLOCK_SIZE]) {
+    let mut generic_block = GenericArray::clone_from_slice(block);
+    match key {
+        AesKey::Aes128(bytes) => Aes128::new(GenericArray::from_slice(bytes)).encrypt_block(&mut generic_block),
+        AesKey::Aes256(bytes) => Aes256::new(GenericArray::from_slice(bytes)).encrypt_block(&mut generic_block),
+    }
+    block.copy_from_slice(&generic_block);
+}
+
+fn increment_counter(counter: &mut [u8; BLOCK_SIZE]) {
+    for byte in counter.iter_mut().rev() {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            break;
+        }
+    }
+}
+
+/// XORs `data` in place with the AES-CTR keystream derived from `key` and
+/// `iv`, treating `iv` as the initial counter and incrementing it
+/// big-endian once per 16-byte block. Encryption and decryption are the
+/// same operation.
+fn apply_keystream(data: &mut [u8], key: &AesKey, iv: [u8; BLOCK_SIZE]) {
+    let mut counter = iv;
+
+    for chunk in data.chunks_mut(BLOCK_SIZE) {
+        let mut keystream = counter;
+        encrypt_block(key, &mut keystream);
+
+        for (byte, ks) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= ks;
+        }
+
+        increment_counter(&mut counter);
+    }
+}
+
+/// Encrypts `plaintext` with AES-CTR and writes `path` as the random 16-byte
+/// IV followed by the ciphertext.
+pub fn encrypt_to_file(plaintext: &[u8], key: &AesKey, path: &str) -> io::Result<()> {
+    let mut iv = [0u8; BLOCK_SIZE];
+    rand::thread_rng().fill_bytes(&mut iv);
+
+    let mut ciphertext = plaintext.to_vec();
+    apply_keystream(&mut ciphertext, key, iv);
+
+    let mut file = File::create(path)?;
+    file.write_all(&iv)?;
+    file.write_all(&ciphertext)?;
+    Ok(())
+}
+
+/// Reads an IV-prefixed ciphertext file written by `encrypt_to_file` and
+/// returns the decrypted plaintext.
+pub fn decrypt_from_file(path: &str, key: &AesKey) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+
+    if contents.len() < BLOCK_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file is too short to contain an IV header",
+        ));
+    }
+
+    let mut iv = [0u8; BLOCK_SIZE];
+    iv.copy_from_slice(&contents[..BLOCK_SIZE]);
+
+    let mut plaintext = contents[BLOCK_SIZE..].to_vec();
+    apply_keystream(&mut plaintext, key, iv);
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("rust-winapi-crypto-test-{name}-{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn round_trips_a_multi_block_buffer_with_aes128() {
+        let key = AesKey::from_bytes(&[0x11; 16]).unwrap();
+        let path = temp_path("aes128");
+        let plaintext = b"this plaintext is deliberately longer than one 16-byte AES block".to_vec();
+
+        encrypt_to_file(&plaintext, &key, &path).unwrap();
+        let decrypted = decrypt_from_file(&path, &key).unwrap();
+
+        assert_eq!(decrypted, plaintext);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn round_trips_a_multi_block_buffer_with_aes256() {
+        let key = AesKey::from_bytes(&[0x22; 32]).unwrap();
+        let path = temp_path("aes256");
+        let plaintext = b"this plaintext is deliberately longer than one 16-byte AES block".to_vec();
+
+        encrypt_to_file(&plaintext, &key, &path).unwrap();
+        let decrypted = decrypt_from_file(&path, &key).unwrap();
+
+        assert_eq!(decrypted, plaintext);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wrong_key_does_not_recover_the_plaintext() {
+        let key = AesKey::from_bytes(&[0x33; 16]).unwrap();
+        let wrong_key = AesKey::from_bytes(&[0x44; 16]).unwrap();
+        let path = temp_path("wrong-key");
+        let plaintext = b"0123456789abcdef0123456789abcdef".to_vec();
+
+        encrypt_to_file(&plaintext, &key, &path).unwrap();
+        let decrypted = decrypt_from_file(&path, &wrong_key).unwrap();
+
+        assert_ne!(decrypted, plaintext);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_hex_key_rejects_odd_length_input() {
+        assert!(parse_hex_key("abc").is_err());
+    }
+
+    #[test]
+    fn parse_hex_key_rejects_wrong_sized_key() {
+        // 10 bytes decodes fine as hex but isn't a valid AES-128/256 size.
+        assert!(parse_hex_key("00112233445566778899").is_err());
+    }
+
+    #[test]
+    fn parse_hex_key_accepts_a_valid_aes128_key() {
+        assert!(parse_hex_key(&"ab".repeat(16)).is_ok());
+    }
+}
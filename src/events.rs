@@ -0,0 +1,317 @@
+/*
+Copyright (C) 2024 Ivin Joel Abraham
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use serde::{Deserialize, Serialize};
+use serde_xml_rs::from_str;
+use std::collections::HashMap;
+use std::io;
+use win_event_log::prelude::*;
+
+use crate::crypto::AesKey;
+use crate::format::{self, OutputFormat};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventInfo {
+    pub event_id: u32,
+    pub provider_name: String,
+    pub level: u32,
+    pub time_created: String,
+    pub channel: String,
+    pub computer: String,
+    pub data: HashMap<String, String>,
+}
+
+// Structs to deserialize the full Windows Event XML payload into.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Event {
+    pub system: System,
+    #[serde(default)]
+    pub event_data: Option<EventDataBody>,
+    #[serde(default)]
+    pub user_data: Option<UserDataBody>,
+}
+
+/// `<UserData>` wraps an arbitrary, provider-defined XML fragment (commonly
+/// `<EventXML>`, but the schema is entirely up to the provider's manifest)
+/// rather than the flat `Name`/`$value` `<Data>` list `<EventData>` uses, so
+/// there's no generic way to pull named fields out of it the way
+/// `build_data_map` does for `<EventData>`. We only track whether the
+/// element was present at all, so `from_event` can log when that's the only
+/// body an event had.
+#[derive(Deserialize, Debug, Default)]
+struct UserDataBody {}
+
+/// Fallback used when the `<EventData>`/`<UserData>` body fails to parse, so
+/// the event's header fields can still be kept instead of dropped entirely.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct HeaderOnlyEvent {
+    pub system: System,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct System {
+    pub provider: Provider,
+    #[serde(rename = "EventID")]
+    pub event_id: u32,
+    pub level: u32,
+    pub time_created: TimeCreated,
+    #[serde(default)]
+    pub channel: String,
+    #[serde(default)]
+    pub computer: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TimeCreated {
+    #[serde(rename = "SystemTime")]
+    pub system_time: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Provider {
+    #[serde(rename = "Name")]
+    pub name: String,
+}
+
+/// The `<EventData>` body: zero or more `<Data>` entries, either named
+/// (`<Data Name="...">value</Data>`) or positional (`<Data>value</Data>`).
+#[derive(Deserialize, Debug, Default)]
+struct EventDataBody {
+    #[serde(rename = "Data", default)]
+    pub entries: Vec<DataEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DataEntry {
+    #[serde(rename = "Name", default)]
+    pub name: Option<String>,
+    #[serde(rename = "$value", default)]
+    pub value: Option<String>,
+}
+
+fn build_data_map(entries: Vec<DataEntry>) -> HashMap<String, String> {
+    let mut data = HashMap::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let key = entry.name.unwrap_or_else(|| format!("Data{}", index + 1));
+        data.insert(key, entry.value.unwrap_or_default());
+    }
+    data
+}
+
+impl EventInfo {
+    fn from_event(event: Event) -> Self {
+        if event.event_data.is_none() {
+            let user_data_note = if event.user_data.is_some() {
+                " (only <UserData>, which isn't parsed into named fields)"
+            } else {
+                ""
+            };
+            eprintln!(
+                "Event {} (provider '{}') has no <EventData>{}; its 'data' map will be empty even though the event body may carry fields",
+                event.system.event_id, event.system.provider.name, user_data_note
+            );
+        }
+
+        let data = event
+            .event_data
+            .map(|body| build_data_map(body.entries))
+            .unwrap_or_default();
+
+        EventInfo {
+            event_id: event.system.event_id,
+            provider_name: event.system.provider.name,
+            level: event.system.level,
+            time_created: event.system.time_created.system_time,
+            channel: event.system.channel,
+            computer: event.system.computer,
+            data,
+        }
+    }
+
+    fn from_header(system: System) -> Self {
+        EventInfo {
+            event_id: system.event_id,
+            provider_name: system.provider.name,
+            level: system.level,
+            time_created: system.time_created.system_time,
+            channel: system.channel,
+            computer: system.computer,
+            data: HashMap::new(),
+        }
+    }
+}
+
+pub fn build_event_query(channels: &[String], min_level: u32) -> QueryList {
+    // Windows event levels run 1 (Critical, most severe) to 5 (Verbose,
+    // least severe), so "at or above this severity" means Level <= min_level.
+    let conditions = vec![Condition::filter(EventFilter::level(
+        min_level,
+        Comparison::LessThanOrEqual,
+    ))];
+
+    let mut query = Query::new();
+    for channel in channels {
+        query = query.item(
+            QueryItem::selector(channel.clone())
+                .system_conditions(Condition::or(conditions.clone()))
+                .build(),
+        );
+    }
+
+    QueryList::new().with_query(query.query()).build()
+}
+
+pub fn fetch_and_parse_events(query: QueryList) -> Vec<EventInfo> {
+    match WinEvents::get(query) {
+        Ok(events) => {
+            let mut extracted_events: Vec<EventInfo> = Vec::new();
+
+            for event in events {
+                let event_xml = event.to_string();
+                match from_str::<Event>(&event_xml) {
+                    Ok(parsed_event) => extracted_events.push(EventInfo::from_event(parsed_event)),
+                    Err(e) => {
+                        eprintln!("Error parsing event body, keeping header fields only: {}", e);
+                        match from_str::<HeaderOnlyEvent>(&event_xml) {
+                            Ok(header_only) => extracted_events.push(EventInfo::from_header(header_only.system)),
+                            Err(e) => eprintln!("Error parsing event header: {}", e),
+                        }
+                    }
+                }
+            }
+            extracted_events
+        }
+        Err(e) => {
+            eprintln!("Error fetching events: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+pub fn save_events_to_file(
+    events: Vec<EventInfo>,
+    file_name: &str,
+    format: OutputFormat,
+    encryption_key: Option<&AesKey>,
+) -> io::Result<()> {
+    match encryption_key {
+        Some(key) => format::encode(&events, &events, format)
+            .and_then(|buffer| crate::crypto::encrypt_to_file(&buffer, key, file_name)),
+        None => format::write_formatted(&events, &events, format, file_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_xml(body: &str) -> String {
+        format!(
+            r#"<Event>
+                <System>
+                    <Provider Name="Microsoft-Windows-Kernel-General"/>
+                    <EventID>16</EventID>
+                    <Level>4</Level>
+                    <TimeCreated SystemTime="2024-01-01T00:00:00.000000000Z"/>
+                    <Channel>System</Channel>
+                    <Computer>HOST1</Computer>
+                </System>
+                {body}
+            </Event>"#
+        )
+    }
+
+    #[test]
+    fn parses_named_event_data_fields() {
+        let xml = sample_xml(
+            r#"<EventData>
+                <Data Name="FieldA">ValueA</Data>
+                <Data Name="FieldB">ValueB</Data>
+            </EventData>"#,
+        );
+
+        let event = EventInfo::from_event(from_str(&xml).unwrap());
+
+        assert_eq!(event.data.get("FieldA"), Some(&"ValueA".to_string()));
+        assert_eq!(event.data.get("FieldB"), Some(&"ValueB".to_string()));
+        assert_eq!(event.data.len(), 2);
+    }
+
+    #[test]
+    fn parses_positional_event_data_fields() {
+        let xml = sample_xml(
+            r#"<EventData>
+                <Data>FirstValue</Data>
+                <Data>SecondValue</Data>
+            </EventData>"#,
+        );
+
+        let event = EventInfo::from_event(from_str(&xml).unwrap());
+
+        assert_eq!(event.data.get("Data1"), Some(&"FirstValue".to_string()));
+        assert_eq!(event.data.get("Data2"), Some(&"SecondValue".to_string()));
+        assert_eq!(event.data.len(), 2);
+    }
+
+    #[test]
+    fn parses_mixed_named_and_positional_event_data_fields() {
+        let xml = sample_xml(
+            r#"<EventData>
+                <Data Name="FieldA">ValueA</Data>
+                <Data>PositionalValue</Data>
+            </EventData>"#,
+        );
+
+        let event = EventInfo::from_event(from_str(&xml).unwrap());
+
+        assert_eq!(event.data.get("FieldA"), Some(&"ValueA".to_string()));
+        // The positional entry is the second <Data> overall, so it keeps
+        // index 2 even though it's the only one without a Name.
+        assert_eq!(event.data.get("Data2"), Some(&"PositionalValue".to_string()));
+        assert_eq!(event.data.len(), 2);
+    }
+
+    #[test]
+    fn header_only_fallback_keeps_header_fields_with_an_empty_data_map() {
+        // Mirrors what `fetch_and_parse_events` does once `from_str::<Event>`
+        // fails on a body it can't make sense of: it re-parses the same XML
+        // as `HeaderOnlyEvent`, which only looks at `<System>`.
+        let xml = sample_xml("<EventData><Data Name=\"FieldA\">ValueA</Data></EventData>");
+
+        let header_only: HeaderOnlyEvent = from_str(&xml).unwrap();
+        let event = EventInfo::from_header(header_only.system);
+
+        assert_eq!(event.event_id, 16);
+        assert_eq!(event.provider_name, "Microsoft-Windows-Kernel-General");
+        assert_eq!(event.channel, "System");
+        assert_eq!(event.computer, "HOST1");
+        assert!(event.data.is_empty());
+    }
+
+    #[test]
+    fn user_data_only_events_keep_header_fields_with_an_empty_data_map() {
+        let xml = sample_xml("<UserData><EventXML><Detail>something</Detail></EventXML></UserData>");
+
+        let event = EventInfo::from_event(from_str(&xml).unwrap());
+
+        assert_eq!(event.event_id, 16);
+        assert!(event.data.is_empty());
+    }
+}
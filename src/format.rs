@@ -0,0 +1,81 @@
+/*
+Copyright (C) 2024 Ivin Joel Abraham
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::cli::FormatArg;
+
+/// Encoding used when writing a collector's results to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    JsonPretty,
+    JsonCompact,
+    Ndjson,
+    MessagePack,
+}
+
+impl From<FormatArg> for OutputFormat {
+    fn from(arg: FormatArg) -> Self {
+        match arg {
+            FormatArg::Json => OutputFormat::JsonPretty,
+            FormatArg::JsonCompact => OutputFormat::JsonCompact,
+            FormatArg::Ndjson => OutputFormat::Ndjson,
+            FormatArg::MessagePack => OutputFormat::MessagePack,
+        }
+    }
+}
+
+/// Encodes `whole` (the full collection, e.g. a `ProcessPortList`) or, for
+/// line-oriented formats, each of `items` individually, in the requested
+/// `format`.
+pub fn encode<W, I>(whole: &W, items: &[I], format: OutputFormat) -> io::Result<Vec<u8>>
+where
+    W: Serialize,
+    I: Serialize,
+{
+    let mut buffer = Vec::new();
+
+    match format {
+        OutputFormat::JsonPretty => serde_json::to_writer_pretty(&mut buffer, whole)?,
+        OutputFormat::JsonCompact => serde_json::to_writer(&mut buffer, whole)?,
+        OutputFormat::Ndjson => {
+            for item in items {
+                serde_json::to_writer(&mut buffer, item)?;
+                buffer.push(b'\n');
+            }
+        }
+        OutputFormat::MessagePack => {
+            rmp_serde::encode::write(&mut buffer, whole)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        }
+    }
+
+    Ok(buffer)
+}
+
+/// Encodes `whole`/`items` per `encode` and writes the result to `path`.
+pub fn write_formatted<W, I>(whole: &W, items: &[I], format: OutputFormat, path: &str) -> io::Result<()>
+where
+    W: Serialize,
+    I: Serialize,
+{
+    let buffer = encode(whole, items, format)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&buffer)?;
+    writer.flush()
+}
@@ -14,203 +14,139 @@ GNU General Public License for more details.
 You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
-use serde::{Deserialize, Serialize};
-use serde_json::to_writer_pretty;
-use serde_xml_rs::from_str;
-use std::fs::File;
-use std::io::{self, BufWriter};
-use std::process::Command;
-use sysinfo::{Pid, ProcessesToUpdate, System as SysSystem};
-use win_event_log::prelude::*;
-
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-struct EventInfo {
-    pub event_id: u32,
-    pub provider_name: String,
-    pub level: u32,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct ProcessInfo {
-    pid: i32,
-    name: String,
-    ports: Vec<u16>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct ProcessPortList {
-    processes: Vec<ProcessInfo>,
-}
+mod cli;
+mod crypto;
+mod events;
+mod format;
+mod ports;
+mod server;
+
+use clap::Parser;
+use cli::{AllArgs, Cli, Commands, DecryptArgs, EncryptionArgs, EventsArgs, PortsArgs};
+use crypto::AesKey;
+use events::{build_event_query, fetch_and_parse_events, save_events_to_file};
+use ports::{get_netstat_output, match_processes_to_ports, parse_netstat_output, save_process_info_to_file};
+use std::io;
+use std::time::Duration;
+use sysinfo::System as SysSystem;
+
+/// Parses `--key` when `--encrypt` was requested (clap guarantees `key` is
+/// present whenever `encrypt` is set, via `requires = "key"`). A malformed
+/// key is ordinary bad input, not a programmer error, so it's surfaced as an
+/// `Err` for the caller to report with `eprintln!` rather than a panic.
+fn resolve_encryption_key(args: &EncryptionArgs) -> io::Result<Option<AesKey>> {
+    if !args.encrypt {
+        return Ok(None);
+    }
 
-// Struct to deserialize the XML data into
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-struct Event {
-    pub system: System,
+    let key = args.key.as_deref().expect("clap requires --key with --encrypt");
+    crypto::parse_hex_key(key).map(Some)
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-struct System {
-    pub provider: Provider,
-    #[serde(rename="EventID")]
-    pub event_id: u32,
-    pub level: u32,
-}
+fn run_ports(args: &PortsArgs) {
+    if args.monitor {
+        println!(
+            "Monitoring ports every {}s, appending deltas to '{}'",
+            args.interval, args.output
+        );
+        if let Err(e) = ports::run_monitor(args, Duration::from_secs(args.interval)) {
+            eprintln!("Error monitoring ports: {}", e);
+        }
+        return;
+    }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-struct Provider {
-    pub name: String,
-}
+    let netstat_output = get_netstat_output();
+    let process_ports = parse_netstat_output(netstat_output);
 
-fn save_events_to_file(events: Vec<EventInfo>, file_name: &str) -> io::Result<()> {
-    let file = File::create(file_name)?;
-    let writer = BufWriter::new(file);
-    to_writer_pretty(writer, &events)?;
-    Ok(())
-}
+    let system = SysSystem::new_all();
 
-fn get_netstat_output() -> Vec<String> {
-    let output = Command::new("netstat")
-        .arg("-no")
-        .output()
-        .expect("Failed to run netstat");
+    let process_info_list = match_processes_to_ports(system, process_ports, args.with_metrics);
+    let encryption_key = match resolve_encryption_key(&args.encryption) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Error: invalid --key: {}", e);
+            return;
+        }
+    };
+    save_process_info_to_file(process_info_list, &args.output, args.format.into(), encryption_key.as_ref());
 
-    String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .map(|line| line.to_string())
-        .collect()
+    println!("Process and port data saved to '{}'", args.output);
 }
 
-fn parse_netstat_output(output: Vec<String>) -> Vec<(u16, i32)> {
-    let mut process_ports = Vec::new();
-
-    for line in output {
-        if line.starts_with("Proto") || line.is_empty() {
-            continue;
+fn run_events(args: &EventsArgs) {
+    let query = build_event_query(&args.channels, args.min_level);
+    let extracted_events = fetch_and_parse_events(query);
+    let encryption_key = match resolve_encryption_key(&args.encryption) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Error: invalid --key: {}", e);
+            return;
         }
+    };
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 5 {
-            if let Some(port_str) = parts[1].split(':').last() {
-                if let Ok(port) = port_str.parse::<u16>() {
-                    if let Ok(pid) = parts[4].parse::<i32>() {
-                        process_ports.push((port, pid));
-                    }
-                }
-            }
-        }
+    if let Err(e) = save_events_to_file(extracted_events, &args.output, args.format.into(), encryption_key.as_ref()) {
+        eprintln!("Error saving events to file: {}", e);
+    } else {
+        println!("Events saved to '{}'", args.output);
     }
-
-    process_ports
 }
 
-fn match_processes_to_ports(
-    mut system: SysSystem,
-    process_ports: Vec<(u16, i32)>,
-) -> Vec<ProcessInfo> {
-    let mut process_info_list: Vec<ProcessInfo> = Vec::new();
-
-    system.refresh_processes(ProcessesToUpdate::All, true);
-    let processes = system.processes();
-
-    for (port, pid) in process_ports {
-        if let Some(process) = processes.get(&Pid::from_u32(pid.try_into().unwrap())) {
-            if let Some(info) = process_info_list.iter_mut().find(|p| p.pid == pid) {
-                info.ports.push(port);
-            } else {
-                process_info_list.push(ProcessInfo {
-                    pid,
-                    name: process.name().to_string_lossy().into_owned(),
-                    ports: vec![port],
-                });
-            }
-        }
+/// `--monitor` isn't supported here: `run_ports` would hand off to
+/// `ports::run_monitor`'s infinite scan loop, so `run_events` would never
+/// run and `events_output` would never be written. Use `rust-winapi ports
+/// --monitor` on its own if you just want the port deltas.
+fn run_all(args: &AllArgs) {
+    if args.monitor {
+        eprintln!("Error: --monitor is not supported with the 'all' subcommand, since it never returns to collect events; run 'ports --monitor' on its own instead");
+        return;
     }
 
-    process_info_list
-}
-
-fn save_process_info_to_file(process_info_list: Vec<ProcessInfo>, file_name: &str) {
-    let file = File::create(file_name).expect("Unable to create file");
-    let process_port_list = ProcessPortList {
-        processes: process_info_list,
+    let ports_args = PortsArgs {
+        output: args.ports_output.clone(),
+        format: args.format,
+        monitor: args.monitor,
+        interval: args.interval,
+        with_metrics: args.with_metrics,
+        encryption: args.encryption.clone(),
+    };
+    let events_args = EventsArgs {
+        output: args.events_output.clone(),
+        format: args.format,
+        min_level: args.min_level,
+        channels: args.channels.clone(),
+        encryption: args.encryption.clone(),
     };
 
-    to_writer_pretty(file, &process_port_list).expect("Failed to write JSON to file");
+    run_ports(&ports_args);
+    run_events(&events_args);
 }
 
-fn fetch_and_parse_events(query: QueryList) -> Vec<EventInfo> {
-    match WinEvents::get(query) {
-        Ok(events) => {
-            let mut extracted_events: Vec<EventInfo> = Vec::new();
-
-            for event in events {
-                let event_xml = event.to_string();
-                match from_str::<Event>(&event_xml) {
-                    Ok(parsed_event) => {
-                        extracted_events.push(EventInfo {
-                            event_id: parsed_event.system.event_id,
-                            provider_name: parsed_event.system.provider.name,
-                            level: parsed_event.system.level,
-                        });
-                    }
-                    Err(e) => {
-                        eprintln!("Error parsing event: {}", e);
-                    }
-                }
-            }
-            extracted_events
-        }
+fn run_decrypt(args: &DecryptArgs) {
+    let key = match crypto::parse_hex_key(&args.key) {
+        Ok(key) => key,
         Err(e) => {
-            eprintln!("Error fetching events: {}", e);
-            Vec::new()
+            eprintln!("Error: invalid --key: {}", e);
+            return;
         }
+    };
+
+    match crypto::decrypt_from_file(&args.input, &key) {
+        Ok(plaintext) => match std::fs::write(&args.output, plaintext) {
+            Ok(()) => println!("Decrypted '{}' to '{}'", args.input, args.output),
+            Err(e) => eprintln!("Error writing decrypted output: {}", e),
+        },
+        Err(e) => eprintln!("Error decrypting '{}': {}", args.input, e),
     }
 }
 
 fn main() {
-    // Exposed Ports Collector 
-    let netstat_output = get_netstat_output();
-    let process_ports = parse_netstat_output(netstat_output);
-
-    let system = SysSystem::new_all();
-
-    let process_info_list = match_processes_to_ports(system, process_ports);
-    save_process_info_to_file(process_info_list, "process_ports.json");
-
-    println!("Process and port data saved to 'process_ports.json'");
-
-    // Event Logger
-    let conditions = vec![Condition::filter(EventFilter::level(
-        1,
-        Comparison::GreaterThanOrEqual,
-    ))];
-
-    let query = QueryList::new()
-        .with_query(
-            Query::new()
-                .item(
-                    QueryItem::selector("Application".to_owned())
-                        .system_conditions(Condition::or(conditions.clone()))
-                        .build(),
-                )
-                .item(
-                    QueryItem::selector("System".to_owned())
-                        .system_conditions(Condition::or(conditions))
-                        .build(),
-                )
-                .query(),
-        )
-        .build();
-
-    let extracted_events = fetch_and_parse_events(query);
-
-    if let Err(e) = save_events_to_file(extracted_events, "events.json") {
-        eprintln!("Error saving events to file: {}", e);
-    } else {
-        println!("Events saved to 'events.json'");
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Commands::Ports(args) => run_ports(args),
+        Commands::Events(args) => run_events(args),
+        Commands::All(args) => run_all(args),
+        Commands::Decrypt(args) => run_decrypt(args),
+        Commands::Serve(args) => server::run(args),
     }
 }
\ No newline at end of file
@@ -0,0 +1,261 @@
+/*
+Copyright (C) 2024 Ivin Joel Abraham
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::collections::{HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::ServeArgs;
+use crate::events::{build_event_query, fetch_and_parse_events};
+use crate::ports::{get_netstat_output, match_processes_to_ports, parse_netstat_output, ProcessPortList};
+use sysinfo::System as SysSystem;
+
+/// How often `stream_events_to` wakes up to check whether its client is
+/// still alive, even if no event has arrived to forward.
+const LIVENESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct Subscriber {
+    min_level: u32,
+    sender: Sender<String>,
+    /// Flipped to `false` by the reader thread spawned in `stream_events_to`
+    /// as soon as the client's socket is closed, so a dead subscriber is
+    /// reaped even if no future event ever matches its `min_level`.
+    alive: Arc<AtomicBool>,
+}
+
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+
+/// How many distinct serialized events `poll_and_broadcast` remembers before
+/// forgetting the oldest ones, so a `serve` process running for days doesn't
+/// grow its dedup set without bound.
+const SEEN_EVENTS_CAPACITY: usize = 4096;
+
+/// A bounded "have we broadcast this event already" set: insertion order is
+/// tracked in `order` so the oldest entry can be evicted once `set` grows
+/// past `SEEN_EVENTS_CAPACITY`.
+struct SeenEvents {
+    set: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenEvents {
+    fn new() -> Self {
+        SeenEvents {
+            set: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Returns `true` if `serialized` hadn't been seen before, recording it.
+    fn insert(&mut self, serialized: String) -> bool {
+        if !self.set.insert(serialized.clone()) {
+            return false;
+        }
+
+        self.order.push_back(serialized);
+        if self.order.len() > SEEN_EVENTS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+
+        true
+    }
+}
+
+/// Runs the `serve` subcommand: accepts client connections on `args.listen`
+/// and answers `PORTS` (a one-shot snapshot) and `SUBSCRIBE <min_level>` (a
+/// live stream of matching events) over a line-based TCP protocol.
+pub fn run(args: &ServeArgs) {
+    let listener = match TcpListener::bind(&args.listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Error binding '{}': {}", args.listen, e);
+            return;
+        }
+    };
+
+    println!("Serving events and ports on {}", args.listen);
+
+    let subscribers: Subscribers = Arc::new(Mutex::new(Vec::new()));
+
+    let poll_args = args.clone();
+    let poll_subscribers = Arc::clone(&subscribers);
+    thread::spawn(move || poll_and_broadcast(&poll_args, poll_subscribers));
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let subscribers = Arc::clone(&subscribers);
+                thread::spawn(move || handle_client(stream, subscribers));
+            }
+            Err(e) => eprintln!("Error accepting connection: {}", e),
+        }
+    }
+}
+
+fn handle_client(stream: TcpStream, subscribers: Subscribers) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Error cloning client stream: {}", e);
+            return;
+        }
+    };
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        let mut parts = line.trim().splitn(2, ' ');
+        match parts.next() {
+            Some("PORTS") => {
+                let list = current_port_list();
+                if write_json_line(&mut writer, &list).is_err() {
+                    break;
+                }
+            }
+            Some("SUBSCRIBE") => {
+                let min_level: u32 = parts.next().and_then(|level| level.parse().ok()).unwrap_or(1);
+                stream_events_to(reader, writer, min_level, &subscribers);
+                return;
+            }
+            _ => {
+                let _ = writeln!(writer, "{{\"error\":\"unknown command, expected PORTS or SUBSCRIBE <min_level>\"}}");
+            }
+        }
+    }
+}
+
+/// Registers `writer` as a subscriber and blocks, forwarding every future
+/// event at or above `min_level` until the client disconnects.
+///
+/// Liveness isn't just inferred from a failed `write_all`: `reader` (the
+/// same connection, still open from before the `SUBSCRIBE` command) is
+/// handed to a background thread that blocks on reads and flips `alive` to
+/// `false` the moment the client closes its end, even if no event ever
+/// arrives to trigger a write. The forwarding loop below wakes up every
+/// `LIVENESS_POLL_INTERVAL` via `recv_timeout` to check `alive`, so a client that disconnects without a
+/// matching event doesn't leak its `Subscriber` or this thread forever.
+fn stream_events_to(reader: BufReader<TcpStream>, mut writer: TcpStream, min_level: u32, subscribers: &Subscribers) {
+    let (sender, receiver) = mpsc::channel();
+    let alive = Arc::new(AtomicBool::new(true));
+    subscribers.lock().unwrap().push(Subscriber {
+        min_level,
+        sender,
+        alive: Arc::clone(&alive),
+    });
+
+    let reader_alive = Arc::clone(&alive);
+    thread::spawn(move || {
+        let mut reader = reader;
+        let mut discard = String::new();
+        loop {
+            discard.clear();
+            // Clients aren't expected to send anything after SUBSCRIBE; this
+            // read only exists to notice EOF/errors when the socket closes.
+            match reader.read_line(&mut discard) {
+                Ok(0) | Err(_) => {
+                    reader_alive.store(false, Ordering::SeqCst);
+                    return;
+                }
+                Ok(_) => {}
+            }
+        }
+    });
+
+    loop {
+        if !alive.load(Ordering::SeqCst) {
+            break;
+        }
+        match receiver.recv_timeout(LIVENESS_POLL_INTERVAL) {
+            Ok(line) => {
+                if writer.write_all(line.as_bytes()).is_err() || writer.write_all(b"\n").is_err() {
+                    alive.store(false, Ordering::SeqCst);
+                    break;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    let _ = writer.shutdown(Shutdown::Both);
+}
+
+fn write_json_line<T: serde::Serialize>(writer: &mut TcpStream, value: &T) -> std::io::Result<()> {
+    let line = serde_json::to_string(value)?;
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")
+}
+
+fn current_port_list() -> ProcessPortList {
+    let netstat_output = get_netstat_output();
+    let process_ports = parse_netstat_output(netstat_output);
+    let system = SysSystem::new_all();
+
+    ProcessPortList {
+        processes: match_processes_to_ports(system, process_ports, false),
+    }
+}
+
+/// Polls the event log on a fixed interval and pushes newly observed events
+/// to every subscriber whose `min_level` the event satisfies.
+fn poll_and_broadcast(args: &ServeArgs, subscribers: Subscribers) {
+    let mut seen = SeenEvents::new();
+
+    loop {
+        let query = build_event_query(&args.channels, args.min_level);
+        let events = fetch_and_parse_events(query);
+
+        for event in &events {
+            let Ok(serialized) = serde_json::to_string(event) else {
+                continue;
+            };
+            if !seen.insert(serialized.clone()) {
+                continue;
+            }
+
+            let mut subs = subscribers.lock().unwrap();
+            subs.retain(|sub| {
+                if !sub.alive.load(Ordering::SeqCst) {
+                    return false;
+                }
+                // Lower Windows event levels are more severe, so "at or
+                // above min_level severity" means event.level <= min_level.
+                if event.level > sub.min_level {
+                    return true;
+                }
+                sub.sender.send(serialized.clone()).is_ok()
+            });
+        }
+
+        // Reap subscribers whose clients disconnected even when no event
+        // arrived this cycle to trigger the retain() above.
+        subscribers.lock().unwrap().retain(|sub| sub.alive.load(Ordering::SeqCst));
+
+        thread::sleep(Duration::from_secs(args.poll_interval));
+    }
+}